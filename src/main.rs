@@ -1,9 +1,14 @@
+use std::collections::HashMap;
 use std::env;
-use std::fs::{read, write};
+use std::fs::{read, read_to_string, write};
+use std::hint::black_box;
+use std::io::{stdin, Read};
+use std::time::Instant;
 
 const BYTE_REGISTERS: [&str; 8] = ["al", "cl", "dl", "bl", "ah", "ch", "dh", "bh"];
 const WORD_REGISTERS: [&str; 8] = ["ax", "cx", "dx", "bx", "sp", "bp", "si", "di"];
 const REGISTER_ENCODINGS: [[&str; 8]; 2] = [BYTE_REGISTERS, WORD_REGISTERS];
+const SEGMENT_REGISTERS: [&str; 4] = ["es", "cs", "ss", "ds"];
 
 const RM_ADDRESS_CALCULATION_ENCODINGS: [&str; 8] = [
     "[bx + si]",
@@ -223,7 +228,7 @@ fn as_opcode_enum(bytes: [u8; 2]) -> Option<Opcode> {
     None
 }
 
-fn parse_register_or_memory_to_or_from_register(bytes: &Vec<u8>, cursor: &mut usize) -> String {
+fn parse_register_or_memory_to_or_from_register(bytes: &[u8], cursor: &mut usize) -> String {
     let first_byte = bytes[*cursor];
     let second_byte = bytes[*cursor + 1];
     *cursor += 2;
@@ -248,7 +253,7 @@ fn parse_register_or_memory_to_or_from_register(bytes: &Vec<u8>, cursor: &mut us
 
                     let is_displacement_signed = ((disp_lo >> 7) & 0x1) == 1;
                     let displacement = if is_displacement_signed {
-                        (disp_lo.wrapping_neg() as i16) * -1
+                        -(disp_lo.wrapping_neg() as i16)
                     } else {
                         disp_lo as i16
                     };
@@ -267,12 +272,12 @@ fn parse_register_or_memory_to_or_from_register(bytes: &Vec<u8>, cursor: &mut us
         0x1 => {
             let is_displacement_signed = ((bytes[*cursor] >> 7) & 0x1) == 1;
             let displacement = if is_displacement_signed {
-                (bytes[*cursor].wrapping_neg() as i16) * -1
+                -(bytes[*cursor].wrapping_neg() as i16)
             } else {
                 bytes[*cursor] as i16
             };
             *cursor += 1;
-            rm_address_calculation_displaced(&rm_bits, &(displacement as i16))
+            rm_address_calculation_displaced(&rm_bits, &displacement)
         }
         0x2 => {
             let displacement = i16::from_ne_bytes([bytes[*cursor], bytes[*cursor + 1]]);
@@ -286,7 +291,7 @@ fn parse_register_or_memory_to_or_from_register(bytes: &Vec<u8>, cursor: &mut us
     let destination = if d_bit == 1 { register } else { &rm };
     let source = if d_bit == 1 { &rm } else { register };
 
-    let operation = if first_byte >> 2 == 0b10010 {
+    let operation = if first_byte >> 2 == 0b100010 {
         "mov"
     } else if first_byte >> 2 == 0b0 {
         "add"
@@ -297,10 +302,10 @@ fn parse_register_or_memory_to_or_from_register(bytes: &Vec<u8>, cursor: &mut us
     } else {
         ""
     };
-    String::from(format!("{operation} {destination}, {source}"))
+    format!("{operation} {destination}, {source}")
 }
 
-fn parse_immediate_to_register(bytes: &Vec<u8>, cursor: &mut usize) -> String {
+fn parse_immediate_to_register(bytes: &[u8], cursor: &mut usize) -> String {
     let first_byte = bytes[*cursor];
     let data_lo = bytes[*cursor + 1];
     *cursor += 2;
@@ -323,7 +328,7 @@ fn parse_immediate_to_register(bytes: &Vec<u8>, cursor: &mut usize) -> String {
     format!("mov {register}, {immediate}")
 }
 
-fn parse_immediate_to_register_or_memory(bytes: &Vec<u8>, cursor: &mut usize) -> String {
+fn parse_immediate_to_register_or_memory(bytes: &[u8], cursor: &mut usize) -> String {
     let first_byte = bytes[*cursor];
     let second_byte = bytes[*cursor + 1];
     *cursor += 2;
@@ -344,7 +349,7 @@ fn parse_immediate_to_register_or_memory(bytes: &Vec<u8>, cursor: &mut usize) ->
 
                     let is_displacement_signed = ((disp_lo >> 7) & 0x1) == 1;
                     let displacement = if is_displacement_signed {
-                        (disp_lo.wrapping_neg() as i16) * -1
+                        -(disp_lo.wrapping_neg() as i16)
                     } else {
                         disp_lo as i16
                     };
@@ -366,11 +371,11 @@ fn parse_immediate_to_register_or_memory(bytes: &Vec<u8>, cursor: &mut usize) ->
 
             let is_displacement_signed = ((disp_lo >> 7) & 0x1) == 1;
             let displacement = if is_displacement_signed {
-                (disp_lo.wrapping_neg() as i16) * -1
+                -(disp_lo.wrapping_neg() as i16)
             } else {
                 disp_lo as i16
             };
-            rm_address_calculation_displaced(&rm_bits, &(displacement as i16))
+            rm_address_calculation_displaced(&rm_bits, &displacement)
         }
         0x2 => {
             let disp_lo = bytes[*cursor];
@@ -446,7 +451,7 @@ fn parse_immediate_to_register_or_memory(bytes: &Vec<u8>, cursor: &mut usize) ->
     }
 }
 
-fn parse_memory_to_accumulator(bytes: &Vec<u8>, cursor: &mut usize) -> String {
+fn parse_memory_to_accumulator(bytes: &[u8], cursor: &mut usize) -> String {
     let first_byte = bytes[*cursor];
     *cursor += 1;
 
@@ -467,7 +472,7 @@ fn parse_memory_to_accumulator(bytes: &Vec<u8>, cursor: &mut usize) -> String {
     }
 }
 
-fn parse_accumulator_to_memory(bytes: &Vec<u8>, cursor: &mut usize) -> String {
+fn parse_accumulator_to_memory(bytes: &[u8], cursor: &mut usize) -> String {
     let first_byte = bytes[*cursor];
     *cursor += 1;
 
@@ -489,7 +494,7 @@ fn parse_accumulator_to_memory(bytes: &Vec<u8>, cursor: &mut usize) -> String {
     }
 }
 
-fn parse_immediate_to_accumulator(bytes: &Vec<u8>, cursor: &mut usize) -> String {
+fn parse_immediate_to_accumulator(bytes: &[u8], cursor: &mut usize) -> String {
     let first_byte = bytes[*cursor];
     *cursor += 1;
 
@@ -516,7 +521,44 @@ fn parse_immediate_to_accumulator(bytes: &Vec<u8>, cursor: &mut usize) -> String
     }
 }
 
-fn parse_jump(bytes: &Vec<u8>, cursor: &mut usize) -> String {
+fn parse_segment_register_move(bytes: &[u8], cursor: &mut usize) -> String {
+    let first_byte = bytes[*cursor];
+    let second_byte = bytes[*cursor + 1];
+    *cursor += 2;
+
+    let r#mod = second_byte >> 6;
+    let segment = SEGMENT_REGISTERS[((second_byte >> 3) & 0x3) as usize];
+    let rm_bits = second_byte & 0x7;
+
+    // segment registers are always moved as words
+    let rm = match r#mod {
+        0x0 if rm_bits == 0x6 => {
+            let address = u16::from_ne_bytes([bytes[*cursor], bytes[*cursor + 1]]);
+            *cursor += 2;
+            format!("[{address}]")
+        }
+        0x0 => RM_ADDRESS_CALCULATION_ENCODINGS[rm_bits as usize].to_owned(),
+        0x1 => {
+            let displacement = bytes[*cursor] as i8 as i16;
+            *cursor += 1;
+            rm_address_calculation_displaced(&rm_bits, &displacement)
+        }
+        0x2 => {
+            let displacement = i16::from_ne_bytes([bytes[*cursor], bytes[*cursor + 1]]);
+            *cursor += 2;
+            rm_address_calculation_displaced(&rm_bits, &displacement)
+        }
+        _ => WORD_REGISTERS[rm_bits as usize].to_owned(),
+    };
+
+    if first_byte == 0b10001110 {
+        format!("mov {segment}, {rm}")
+    } else {
+        format!("mov {rm}, {segment}")
+    }
+}
+
+fn parse_jump(bytes: &[u8], cursor: &mut usize) -> String {
     let first_byte = bytes[*cursor];
     let ip_inc8 = bytes[*cursor + 1] as i8;
     *cursor += 2;
@@ -548,100 +590,971 @@ fn parse_jump(bytes: &Vec<u8>, cursor: &mut usize) -> String {
     format!("{mnemonic} {ip_inc8}")
 }
 
-fn parse_bin(bin: Vec<u8>) -> String {
+struct Instruction {
+    offset: usize,
+    size: usize,
+    text: String,
+}
+
+// puts the segment inside the memory operand, `[es:bx]`, or in front of
+// the instruction when it has no memory operand
+fn with_segment_override(instruction: &str, segment: &str) -> String {
+    if instruction.contains('[') {
+        instruction.replacen('[', &format!("[{segment}:"), 1)
+    } else {
+        format!("{segment} {instruction}")
+    }
+}
+
+fn decode_instruction(bin: &[u8], cursor: &mut usize) -> Option<String> {
+    // like the cpu, only the last of several override prefixes counts
+    let mut segment_override = None;
+    while let Some(&byte) = bin.get(*cursor) {
+        if byte & 0b11100111 != 0b00100110 {
+            break;
+        }
+        segment_override = Some(SEGMENT_REGISTERS[((byte >> 3) & 0x3) as usize]);
+        *cursor += 1;
+    }
+
+    let instruction = decode_unprefixed_instruction(bin, cursor)?;
+    match segment_override {
+        Some(segment) => Some(with_segment_override(&instruction, segment)),
+        None => Some(instruction),
+    }
+}
+
+fn decode_unprefixed_instruction(bin: &[u8], cursor: &mut usize) -> Option<String> {
+    let first_two_bytes = [*bin.get(*cursor)?, *bin.get(*cursor + 1).unwrap_or(&0)];
+
+    let op = as_opcode_enum(first_two_bytes)?;
+
+    match op {
+        Opcode::MovRegisterOrMemoryToOrFromRegister
+        | Opcode::AddRegisterOrMemoryWithRegisterToEither
+        | Opcode::SubRegisterOrMemoryWithRegisterToEither
+        | Opcode::CmpRegisterOrMemoryAndRegister => {
+            Some(parse_register_or_memory_to_or_from_register(bin, cursor))
+        }
+        Opcode::MovImmediateToRegister => Some(parse_immediate_to_register(bin, cursor)),
+        Opcode::MovImmediateToRegisterOrMemory
+        | Opcode::AddImmediateToRegisterOrMemory
+        | Opcode::SubImmediateToRegisterOrMemory
+        | Opcode::CmpImmediateWithRegisterOrMemory => {
+            Some(parse_immediate_to_register_or_memory(bin, cursor))
+        }
+        Opcode::MovMemoryToAccumulator => Some(parse_memory_to_accumulator(bin, cursor)),
+        Opcode::MovAccumulatorToMemory => Some(parse_accumulator_to_memory(bin, cursor)),
+        Opcode::AddImmediateToAccumulator
+        | Opcode::SubImmediateToAccumulator
+        | Opcode::CmpImmediateWithAccumulator => Some(parse_immediate_to_accumulator(bin, cursor)),
+        Opcode::JumpOnCXZero
+        | Opcode::LoopWhileNotZero
+        | Opcode::LoopWhileZero
+        | Opcode::LoopCXTimes
+        | Opcode::JumpOnNotSign
+        | Opcode::JumpOnNotOverflow
+        | Opcode::JumpOnNotPar
+        | Opcode::JumpOnNotBelowOrEqual
+        | Opcode::JumpOnNotBelow
+        | Opcode::JumpOnNotLessOrEqual
+        | Opcode::JumpOnNotLess
+        | Opcode::JumpOnNotEqual
+        | Opcode::JumpOnSign
+        | Opcode::JumpOnOverflow
+        | Opcode::JumpOnParity
+        | Opcode::JumpOnBelowOrEqual
+        | Opcode::JumpOnBelow
+        | Opcode::JumpOnLessOrEqual
+        | Opcode::JumpOnLess
+        | Opcode::JumpOnEqual => Some(parse_jump(bin, cursor)),
+        Opcode::MovRegisterOrMemoryToSegmentRegister
+        | Opcode::MovSegmentRegisterToRegisterOrMemory => {
+            Some(parse_segment_register_move(bin, cursor))
+        }
+    }
+}
+
+fn decode_bin(bin: &[u8]) -> Vec<Instruction> {
     let mut cursor = 0;
-    let mut asm = String::from("bits 16\n\n");
+    let mut instructions = Vec::new();
 
     while cursor < bin.len() {
-        let first_two_bytes = [bin[cursor], bin[cursor + 1]];
-
-        let op = as_opcode_enum(first_two_bytes)
-            .expect(format!("Unrecognized opcode. {:0>8b}", first_two_bytes[0]).as_str());
-
-        match op {
-            Opcode::MovRegisterOrMemoryToOrFromRegister
-            | Opcode::AddRegisterOrMemoryWithRegisterToEither
-            | Opcode::SubRegisterOrMemoryWithRegisterToEither
-            | Opcode::CmpRegisterOrMemoryAndRegister => {
-                asm.push_str("\n");
-                asm.push_str(&parse_register_or_memory_to_or_from_register(
-                    &bin,
-                    &mut cursor,
-                ));
-            }
-            Opcode::MovImmediateToRegister => {
-                asm.push_str("\n");
-                asm.push_str(&parse_immediate_to_register(&bin, &mut cursor));
-            }
-            Opcode::MovImmediateToRegisterOrMemory
-            | Opcode::AddImmediateToRegisterOrMemory
-            | Opcode::SubImmediateToRegisterOrMemory
-            | Opcode::CmpImmediateWithRegisterOrMemory => {
-                asm.push_str("\n");
-                asm.push_str(&parse_immediate_to_register_or_memory(&bin, &mut cursor));
-            }
-            Opcode::MovMemoryToAccumulator => {
-                asm.push_str("\n");
-                asm.push_str(&parse_memory_to_accumulator(&bin, &mut cursor));
-            }
-            Opcode::MovAccumulatorToMemory => {
-                asm.push_str("\n");
-                asm.push_str(&parse_accumulator_to_memory(&bin, &mut cursor));
-            }
-            Opcode::AddImmediateToAccumulator
-            | Opcode::SubImmediateToAccumulator
-            | Opcode::CmpImmediateWithAccumulator => {
-                asm.push_str("\n");
-                asm.push_str(&parse_immediate_to_accumulator(&bin, &mut cursor));
+        let offset = cursor;
+        let text = decode_instruction(bin, &mut cursor)
+            .unwrap_or_else(|| panic!("Unrecognized opcode. {:0>8b}", bin[offset]));
+
+        instructions.push(Instruction {
+            offset,
+            size: cursor - offset,
+            text,
+        });
+    }
+
+    instructions
+}
+
+#[derive(Default)]
+struct Annotations {
+    names: HashMap<usize, String>,
+    comments: HashMap<usize, String>,
+}
+
+// words nasm would read as something other than a label
+const RESERVED_WORDS: [&str; 26] = [
+    "mov", "add", "sub", "cmp", "je", "jl", "jle", "jb", "jbe", "jp", "jo", "js", "jne", "jnl",
+    "jnle", "jnb", "jnbe", "jnp", "jno", "jns", "loop", "loopz", "loopnz", "jcxz", "byte", "word",
+];
+
+fn is_label_name(value: &str) -> bool {
+    let lowercase = value.to_ascii_lowercase();
+    if RESERVED_WORDS
+        .iter()
+        .chain(&BYTE_REGISTERS)
+        .chain(&WORD_REGISTERS)
+        .chain(&SEGMENT_REGISTERS)
+        .any(|word| *word == lowercase)
+    {
+        return false;
+    }
+
+    let mut chars = value.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' || first == '.' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        }
+        _ => false,
+    }
+}
+
+// one annotation per line, with the offset in hex:
+//   offset: name       labels the instruction at offset
+//   offset: ; comment  appends a comment to it
+fn parse_annotations(text: &str) -> Annotations {
+    let mut annotations = Annotations::default();
+
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (offset, value) = line
+            .split_once(':')
+            .unwrap_or_else(|| panic!("Invalid annotation line: {line}"));
+        let offset = offset.trim();
+        let offset = usize::from_str_radix(offset.trim_start_matches("0x"), 16)
+            .unwrap_or_else(|_| panic!("Invalid annotation offset: {offset}"));
+        let value = value.trim();
+
+        if let Some(comment) = value.strip_prefix(';') {
+            let value = comment.trim();
+            annotations
+                .comments
+                .entry(offset)
+                .and_modify(|comment| {
+                    comment.push_str("; ");
+                    comment.push_str(value);
+                })
+                .or_insert_with(|| value.to_owned());
+        } else if is_label_name(value) {
+            annotations.names.insert(offset, value.to_owned());
+        } else {
+            panic!("Invalid annotation label: {value}");
+        }
+    }
+
+    annotations
+}
+
+fn listing(instructions: &[Instruction], annotations: &Annotations) -> String {
+    format!(
+        "bits 16\n\n{}{}",
+        listing_body(instructions, annotations),
+        unplaced_annotations(instructions, annotations)
+    )
+}
+
+// labels and comments that did not land on a decoded instruction are
+// kept as comments so nothing the user or the file named goes missing
+fn unplaced_annotations(instructions: &[Instruction], annotations: &Annotations) -> String {
+    let decoded_offsets: Vec<usize> = instructions
+        .iter()
+        .map(|instruction| instruction.offset)
+        .collect();
+    let mut leftovers: Vec<(usize, String)> = annotations
+        .names
+        .iter()
+        .map(|(offset, name)| (*offset, name.clone()))
+        .chain(
+            annotations
+                .comments
+                .iter()
+                .map(|(offset, comment)| (*offset, format!("; {comment}"))),
+        )
+        .filter(|(offset, _)| !decoded_offsets.contains(offset))
+        .collect();
+    leftovers.sort();
+
+    let mut asm = String::new();
+    for (offset, text) in leftovers {
+        asm.push_str(&format!("\n; 0x{offset:x}: {text}"));
+    }
+
+    asm
+}
+
+fn listing_body(instructions: &[Instruction], annotations: &Annotations) -> String {
+    let mut asm = String::new();
+
+    for instruction in instructions {
+        if let Some(name) = annotations.names.get(&instruction.offset) {
+            asm.push('\n');
+            asm.push_str(name);
+            asm.push(':');
+        }
+
+        asm.push('\n');
+        asm.push_str(&instruction.text);
+
+        if let Some(comment) = annotations.comments.get(&instruction.offset) {
+            asm.push_str(" ; ");
+            asm.push_str(comment);
+        }
+    }
+
+    asm
+}
+
+// memory operands addressed through bp default to the stack segment,
+// everything else defaults to the data segment unless an override
+// prefix names another one. returns the segment and whether it was
+// overridden
+fn memory_segment(instruction: &str) -> Option<(&'static str, bool)> {
+    for segment in SEGMENT_REGISTERS {
+        if instruction.contains(&format!("[{segment}:")) {
+            return Some((segment, true));
+        }
+    }
+
+    if !instruction.contains('[') {
+        None
+    } else if instruction.contains("[bp") {
+        Some(("ss", false))
+    } else {
+        Some(("ds", false))
+    }
+}
+
+fn count_of(count: usize, singular: &str, plural: &str) -> String {
+    if count == 1 {
+        format!("1 {singular}")
+    } else {
+        format!("{count} {plural}")
+    }
+}
+
+// returns the segment register a `mov` loads and the one it stores, if any
+fn segment_register_move(instruction: &str) -> (Option<&'static str>, Option<&'static str>) {
+    let Some((destination, source)) = instruction
+        .strip_prefix("mov ")
+        .and_then(|operands| operands.split_once(", "))
+    else {
+        return (None, None);
+    };
+
+    let find = |operand: &str| {
+        SEGMENT_REGISTERS
+            .into_iter()
+            .find(|segment| *segment == operand)
+    };
+    (find(destination), find(source))
+}
+
+fn segment_usage_report(bin: &[u8]) -> String {
+    let instructions = decode_prefix(bin);
+    let mut default_accesses = HashMap::new();
+    let mut override_accesses = HashMap::new();
+    let mut loads = HashMap::new();
+    let mut stores = HashMap::new();
+    let mut warnings = String::new();
+
+    for instruction in &instructions {
+        let (loaded, stored) = segment_register_move(&instruction.text);
+        if let Some(segment) = loaded {
+            *loads.entry(segment).or_insert(0) += 1;
+        }
+        if let Some(segment) = stored {
+            *stores.entry(segment).or_insert(0) += 1;
+        }
+
+        match memory_segment(&instruction.text) {
+            Some((segment, true)) => *override_accesses.entry(segment).or_insert(0) += 1,
+            Some((segment, false)) => {
+                *default_accesses.entry(segment).or_insert(0) += 1;
+                if segment == "ss" {
+                    warnings.push_str(&format!(
+                        "warning: 0x{:x}: ss-relative data access without override: {}\n",
+                        instruction.offset, instruction.text
+                    ));
+                }
             }
-            Opcode::JumpOnCXZero
-            | Opcode::LoopWhileNotZero
-            | Opcode::LoopWhileZero
-            | Opcode::LoopCXTimes
-            | Opcode::JumpOnNotSign
-            | Opcode::JumpOnNotOverflow
-            | Opcode::JumpOnNotPar
-            | Opcode::JumpOnNotBelowOrEqual
-            | Opcode::JumpOnNotBelow
-            | Opcode::JumpOnNotLessOrEqual
-            | Opcode::JumpOnNotLess
-            | Opcode::JumpOnNotEqual
-            | Opcode::JumpOnSign
-            | Opcode::JumpOnOverflow
-            | Opcode::JumpOnParity
-            | Opcode::JumpOnBelowOrEqual
-            | Opcode::JumpOnBelow
-            | Opcode::JumpOnLessOrEqual
-            | Opcode::JumpOnLess
-            | Opcode::JumpOnEqual => {
-                asm.push_str("\n");
-                asm.push_str(&parse_jump(&bin, &mut cursor));
+            None => {}
+        }
+    }
+
+    let mut report = String::new();
+    for segment in ["ds", "ss"] {
+        let count = default_accesses.get(segment).copied().unwrap_or(0);
+        let count = count_of(count, "access", "accesses");
+        report.push_str(&format!("default {segment}: {count}\n"));
+    }
+    for segment in SEGMENT_REGISTERS {
+        let count = override_accesses.get(segment).copied().unwrap_or(0);
+        let count = count_of(count, "access", "accesses");
+        report.push_str(&format!("override {segment}: {count}\n"));
+    }
+    for segment in SEGMENT_REGISTERS {
+        let load_count = loads.get(segment).copied().unwrap_or(0);
+        let store_count = stores.get(segment).copied().unwrap_or(0);
+        report.push_str(&format!(
+            "{segment} loaded {}, stored {}\n",
+            count_of(load_count, "time", "times"),
+            count_of(store_count, "time", "times")
+        ));
+    }
+    report.push_str(&warnings);
+
+    if let Some(note) = undecoded_note(&instructions, bin.len()) {
+        report.push_str(&note);
+        report.push('\n');
+    }
+
+    report
+}
+
+// runtime libraries linked into these toolchains' output embed a
+// copyright banner or an error message that is specific enough to tell
+// them apart. this is only a string scan, the startup code itself is
+// not looked at, so toolchains that leave no banner (like A86) are not
+// recognized. a banner matches when all of its parts are found, which
+// lets short runtime strings count only next to their companions
+const TOOLCHAIN_BANNERS: [(&str, &[&str]); 6] = [
+    ("Turbo C", &["Turbo-C - Copyright"]),
+    ("Turbo C", &["Borland C++ - Copyright"]),
+    ("Microsoft C", &["MS Run-Time Library - Copyright"]),
+    ("Microsoft C", &["R6001\r\n- null pointer assignment"]),
+    (
+        "Turbo Pascal",
+        &["Portions Copyright (c) 1983,9", "Borland"],
+    ),
+    ("Turbo Pascal", &["Runtime error ", " at ", "Borland"]),
+];
+
+fn scan_toolchain_banners(bin: &[u8]) -> Vec<&'static str> {
+    let mut toolchains = Vec::new();
+
+    for (toolchain, parts) in TOOLCHAIN_BANNERS {
+        let found = parts.iter().all(|part| {
+            bin.windows(part.len())
+                .any(|window| window == part.as_bytes())
+        });
+
+        if found && !toolchains.contains(&toolchain) {
+            toolchains.push(toolchain);
+        }
+    }
+
+    toolchains
+}
+
+const COVERAGE_UNKNOWN: u8 = 0;
+const COVERAGE_CODE: u8 = 1;
+const COVERAGE_PADDING: u8 = 2;
+
+// the longest instruction the decoder understands without its prefixes,
+// used to keep a truncated instruction at the end of the input from
+// reading past it
+const MAX_INSTRUCTION_SIZE: usize = 6;
+
+// decodes instructions until the first one the decoder does not
+// understand or that is cut off by the end of the input
+fn decode_prefix(bin: &[u8]) -> Vec<Instruction> {
+    decode_padded_prefix(&padded(bin), bin.len())
+}
+
+fn padded(bin: &[u8]) -> Vec<u8> {
+    let mut padded = bin.to_vec();
+    padded.extend_from_slice(&[0; MAX_INSTRUCTION_SIZE]);
+    padded
+}
+
+// `padded` is the input of `length` bytes followed by enough zeros for
+// any instruction cut off at the end to be read without going past it
+fn decode_padded_prefix(padded: &[u8], length: usize) -> Vec<Instruction> {
+    let mut cursor = 0;
+    let mut instructions = Vec::new();
+
+    while cursor < length {
+        let offset = cursor;
+        match decode_instruction(padded, &mut cursor) {
+            Some(text) if cursor <= length => instructions.push(Instruction {
+                offset,
+                size: cursor - offset,
+                text,
+            }),
+            _ => break,
+        }
+    }
+
+    instructions
+}
+
+fn decoded_length(instructions: &[Instruction]) -> usize {
+    instructions
+        .last()
+        .map_or(0, |instruction| instruction.offset + instruction.size)
+}
+
+fn undecoded_note(instructions: &[Instruction], length: usize) -> Option<String> {
+    let decoded = decoded_length(instructions);
+    (decoded < length).then(|| {
+        format!(
+            "; {} from offset 0x{decoded:x}",
+            count_of(length - decoded, "undecoded byte", "undecoded bytes")
+        )
+    })
+}
+
+fn coverage_map(bin: &[u8]) -> Vec<u8> {
+    let mut map = vec![COVERAGE_UNKNOWN; bin.len()];
+    let instructions = decode_prefix(bin);
+
+    for instruction in &instructions {
+        map[instruction.offset..instruction.offset + instruction.size].fill(COVERAGE_CODE);
+    }
+
+    // firmware images are usually filled up to their size with 0x00 or
+    // 0xff. the fill only starts after the last instruction that uses
+    // any other byte, so a trailing immediate of 0xff stays code. runs
+    // of 0x00 decode as `add [bx + si], al` and are still padding
+    let Some(&fill) = bin.last() else {
+        return map;
+    };
+    if fill != 0x00 && fill != 0xff {
+        return map;
+    }
+
+    let mut padding_start = bin.len();
+    while padding_start > 0 && bin[padding_start - 1] == fill {
+        padding_start -= 1;
+    }
+    if let Some(instruction) = instructions.iter().find(|instruction| {
+        instruction.offset < padding_start && padding_start < instruction.offset + instruction.size
+    }) {
+        padding_start = instruction.offset + instruction.size;
+    }
+    map[padding_start..].fill(COVERAGE_PADDING);
+
+    map
+}
+
+// the listing that goes next to a coverage map, which is expected to
+// come from inputs the decoder only partly understands
+fn partial_listing(bin: &[u8], annotations: &Annotations) -> String {
+    let instructions = decode_prefix(bin);
+    let mut asm = format!("bits 16\n\n{}", listing_body(&instructions, annotations));
+
+    if let Some(note) = undecoded_note(&instructions, bin.len()) {
+        asm.push('\n');
+        asm.push_str(&note);
+    }
+    asm.push_str(&unplaced_annotations(&instructions, annotations));
+
+    asm
+}
+
+fn coverage_summary(map: &[u8]) -> String {
+    let total = map.len().max(1) as f64;
+    let mut summary = String::new();
+
+    for (name, class) in [
+        ("code", COVERAGE_CODE),
+        ("padding", COVERAGE_PADDING),
+        ("unknown", COVERAGE_UNKNOWN),
+    ] {
+        let count = map.iter().filter(|&&byte| byte == class).count();
+        summary.push_str(&format!(
+            "{name}: {count} bytes ({:.1}%)\n",
+            count as f64 * 100.0 / total
+        ));
+    }
+
+    summary
+}
+
+// accepts plain hex like `89 d9 b8 e8 03` as well as xxd dumps, whose
+// offset column and trailing ascii column are dropped
+fn parse_hex_text(text: &str) -> Vec<u8> {
+    let mut digits = String::new();
+
+    for line in text.lines() {
+        let mut line = line.trim();
+
+        let first_token = line.split_whitespace().next().unwrap_or("");
+        if first_token.ends_with(':') {
+            line = line[first_token.len()..].trim_start();
+            if let Some((hex, _ascii)) = line.split_once("  ") {
+                line = hex;
             }
-            _ => {
-                panic!("found unimplemented op")
+        }
+
+        for token in line.split(|c: char| c.is_whitespace() || c == ',') {
+            digits.push_str(token.trim_start_matches("0x"));
+        }
+    }
+
+    if !digits.len().is_multiple_of(2) {
+        panic!("Hex input has an odd number of digits");
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .unwrap_or_else(|_| panic!("Invalid hex byte: {}", &digits[i..i + 2]))
+        })
+        .collect()
+}
+
+const BENCH_WARMUP_SAMPLES: usize = 3;
+const BENCH_SAMPLES: usize = 10;
+const BENCH_BYTES_PER_SAMPLE: usize = 1 << 20;
+
+struct BenchStats {
+    mean: f64,
+    min: f64,
+    max: f64,
+    stddev: f64,
+}
+
+fn bench_stats(samples: &[f64]) -> BenchStats {
+    let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+    let min = samples.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = samples.iter().cloned().fold(0.0, f64::max);
+    let stddev = (samples
+        .iter()
+        .map(|seconds| (seconds - mean).powi(2))
+        .sum::<f64>()
+        / samples.len() as f64)
+        .sqrt();
+
+    BenchStats {
+        mean,
+        min,
+        max,
+        stddev,
+    }
+}
+
+fn run_bench(bin: &[u8]) {
+    let instructions = decode_prefix(bin);
+    let decoded = decoded_length(&instructions);
+    if decoded == 0 {
+        panic!("Nothing to benchmark, the first instruction could not be decoded");
+    }
+
+    // only the decoded part is timed, padded once up front so the copy
+    // stays out of the measurement
+    let code = padded(&bin[..decoded]);
+
+    // small inputs are decoded several times per sample so each sample
+    // is long enough for the timer to be meaningful
+    let passes = (BENCH_BYTES_PER_SAMPLE / decoded).max(1);
+
+    let mut samples = Vec::with_capacity(BENCH_SAMPLES);
+    for sample in 0..BENCH_WARMUP_SAMPLES + BENCH_SAMPLES {
+        let start = Instant::now();
+        for _ in 0..passes {
+            black_box(decode_padded_prefix(black_box(&code), decoded));
+        }
+        let seconds = start.elapsed().as_secs_f64() / passes as f64;
+
+        if sample >= BENCH_WARMUP_SAMPLES {
+            samples.push(seconds);
+        }
+    }
+
+    let stats = bench_stats(&samples);
+
+    println!(
+        "input: {} bytes, {decoded} bytes decoded, {} instructions",
+        bin.len(),
+        instructions.len()
+    );
+    if let Some(note) = undecoded_note(&instructions, bin.len()) {
+        println!("{note}");
+    }
+    println!(
+        "decode: mean {:.3} us, min {:.3} us, max {:.3} us, stddev {:.3} us ({BENCH_SAMPLES} samples of {passes} passes)",
+        stats.mean * 1e6,
+        stats.min * 1e6,
+        stats.max * 1e6,
+        stats.stddev * 1e6
+    );
+    println!(
+        "throughput: {:.2} MB/s, {:.0} instructions/s",
+        decoded as f64 / stats.mean / 1e6,
+        instructions.len() as f64 / stats.mean
+    );
+}
+
+const NE_SEGMENT_DATA_FLAG: u16 = 0x0001;
+
+struct NeSegment {
+    number: usize,
+    // None when the segment has no data in the file, like uninitialized
+    // data that the loader only allocates
+    file_offset: Option<usize>,
+    length: usize,
+    is_data: bool,
+}
+
+struct NeExecutable {
+    segments: Vec<NeSegment>,
+    // labels for the entry point and exported entries, by segment number
+    labels: HashMap<usize, Annotations>,
+}
+
+fn read_ne_bytes(bin: &[u8], offset: usize, length: usize) -> &[u8] {
+    bin.get(offset..offset + length).unwrap_or_else(|| {
+        panic!("Malformed NE header: offset {offset:x} is past the end of the file")
+    })
+}
+
+fn read_u8(bin: &[u8], offset: usize) -> u8 {
+    read_ne_bytes(bin, offset, 1)[0]
+}
+
+fn read_u16(bin: &[u8], offset: usize) -> u16 {
+    let bytes = read_ne_bytes(bin, offset, 2);
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+fn parse_ne(bin: &[u8]) -> NeExecutable {
+    if !bin.starts_with(b"MZ") || bin.len() < 0x40 {
+        panic!("Not an MZ executable");
+    }
+
+    let header = u32::from_le_bytes([bin[0x3c], bin[0x3d], bin[0x3e], bin[0x3f]]) as usize;
+    if bin.get(header..header + 2) != Some(b"NE".as_slice()) {
+        panic!("No NE header found");
+    }
+
+    let entry_table = header + read_u16(bin, header + 0x04) as usize;
+    let entry_table_end = entry_table + read_u16(bin, header + 0x06) as usize;
+    let entry_ip = read_u16(bin, header + 0x14) as usize;
+    let entry_cs = read_u16(bin, header + 0x16) as usize;
+    let segment_count = read_u16(bin, header + 0x1c) as usize;
+    let segment_table = header + read_u16(bin, header + 0x22) as usize;
+    let resident_names = header + read_u16(bin, header + 0x26) as usize;
+    // a shift count of 0 stands for the default 512 byte sectors
+    let alignment_shift = match read_u16(bin, header + 0x32) {
+        0 => 9,
+        shift => shift,
+    };
+
+    let mut segments = Vec::with_capacity(segment_count);
+    for index in 0..segment_count {
+        let entry = segment_table + index * 8;
+        let sector = read_u16(bin, entry) as usize;
+        let length = match read_u16(bin, entry + 2) {
+            0 if sector != 0 => 0x10000,
+            length => length as usize,
+        };
+
+        segments.push(NeSegment {
+            number: index + 1,
+            file_offset: (sector != 0).then_some(sector << alignment_shift),
+            length,
+            is_data: read_u16(bin, entry + 4) & NE_SEGMENT_DATA_FLAG != 0,
+        });
+    }
+
+    // the first resident name is the module name, the rest are exported
+    // names followed by their ordinal
+    let mut names_by_ordinal = HashMap::new();
+    let mut cursor = resident_names;
+    while read_u8(bin, cursor) != 0 {
+        let length = read_u8(bin, cursor) as usize;
+        let name = String::from_utf8_lossy(read_ne_bytes(bin, cursor + 1, length)).into_owned();
+        let ordinal = read_u16(bin, cursor + 1 + length);
+        if ordinal != 0 {
+            names_by_ordinal.insert(ordinal, name);
+        }
+        cursor += length + 3;
+    }
+
+    let mut labels: HashMap<usize, Annotations> = HashMap::new();
+    if entry_cs != 0 {
+        labels
+            .entry(entry_cs)
+            .or_default()
+            .names
+            .insert(entry_ip, "start".to_owned());
+    }
+
+    // entries come in bundles of a count and a segment indicator: 0 for
+    // unused ordinals, 0xff for movable entries and a segment number for
+    // fixed ones
+    let mut ordinal: u16 = 1;
+    let mut cursor = entry_table;
+    while cursor < entry_table_end && read_u8(bin, cursor) != 0 {
+        let count = read_u8(bin, cursor) as usize;
+        let indicator = read_u8(bin, cursor + 1);
+        cursor += 2;
+
+        for _ in 0..count {
+            let (segment, offset) = match indicator {
+                0x00 => {
+                    ordinal += 1;
+                    continue;
+                }
+                0xff => {
+                    let entry = (
+                        read_u8(bin, cursor + 3) as usize,
+                        read_u16(bin, cursor + 4) as usize,
+                    );
+                    cursor += 6;
+                    entry
+                }
+                segment => {
+                    let entry = (segment as usize, read_u16(bin, cursor + 1) as usize);
+                    cursor += 3;
+                    entry
+                }
+            };
+
+            let name = match names_by_ordinal.get(&ordinal) {
+                Some(name) if is_label_name(name) => name.clone(),
+                _ => format!("entry_{ordinal}"),
+            };
+            labels
+                .entry(segment)
+                .or_default()
+                .names
+                .entry(offset)
+                .or_insert(name);
+            ordinal += 1;
+        }
+    }
+
+    NeExecutable { segments, labels }
+}
+
+// annotations for an NE executable use file offsets, which are moved to
+// the segment that contains them. user names replace exported names
+fn merge_ne_annotations(executable: &mut NeExecutable, annotations: Annotations) {
+    let segment_offset = |offset: usize| {
+        executable
+            .segments
+            .iter()
+            .find_map(|segment| {
+                let start = segment.file_offset?;
+                (start <= offset && offset < start + segment.length)
+                    .then_some((segment.number, offset - start))
+            })
+            .unwrap_or_else(|| panic!("Annotation offset {offset:x} is outside every NE segment"))
+    };
+
+    let mut names = Vec::new();
+    for (offset, name) in annotations.names {
+        names.push((segment_offset(offset), name));
+    }
+    let mut comments = Vec::new();
+    for (offset, comment) in annotations.comments {
+        comments.push((segment_offset(offset), comment));
+    }
+
+    for ((segment, offset), name) in names {
+        let labels = executable.labels.entry(segment).or_default();
+        labels.names.insert(offset, name);
+    }
+    for ((segment, offset), comment) in comments {
+        let labels = executable.labels.entry(segment).or_default();
+        labels.comments.insert(offset, comment);
+    }
+}
+
+fn ne_listing(executable: &NeExecutable, bin: &[u8]) -> String {
+    let mut asm = String::from("bits 16\n");
+    let no_labels = Annotations::default();
+
+    for segment in &executable.segments {
+        let kind = if segment.is_data { "data" } else { "code" };
+        let location = match segment.file_offset {
+            Some(file_offset) => format!("at file offset 0x{file_offset:x}"),
+            None => "with no data in the file".to_owned(),
+        };
+        asm.push_str(&format!(
+            "\n\n; segment {} ({kind}), {} bytes {location}\nsection seg{} vstart=0\n",
+            segment.number, segment.length, segment.number
+        ));
+
+        let labels = executable.labels.get(&segment.number).unwrap_or(&no_labels);
+        let mut instructions = Vec::new();
+
+        if let (false, Some(file_offset)) = (segment.is_data, segment.file_offset) {
+            let end = (file_offset + segment.length).min(bin.len());
+            let code = &bin[file_offset.min(end)..end];
+            instructions = decode_prefix(code);
+            asm.push_str(&listing_body(&instructions, labels));
+
+            if let Some(note) = undecoded_note(&instructions, code.len()) {
+                asm.push('\n');
+                asm.push_str(&note);
             }
         }
+
+        asm.push_str(&unplaced_annotations(&instructions, labels));
     }
 
     asm
 }
 
+fn decode_hex(hex: &str) -> Vec<String> {
+    let bin = parse_hex_text(hex);
+    let instructions = decode_prefix(&bin);
+    let mut lines: Vec<String> = instructions
+        .iter()
+        .map(|instruction| instruction.text.clone())
+        .collect();
+
+    if let Some(note) = undecoded_note(&instructions, bin.len()) {
+        let leftover: Vec<String> = bin[decoded_length(&instructions)..]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        lines.push(format!("{note}: {}", leftover.join(" ")));
+    }
+
+    lines
+}
+
+// a ctags file pointing every label line of the listing at its line
+// number, sorted by name so editors can binary search it
+fn tags(asm: &str, asm_file: &str) -> String {
+    let mut entries: Vec<(&str, usize)> = asm
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let name = line.strip_suffix(':')?;
+            is_label_name(name).then_some((name, index + 1))
+        })
+        .collect();
+    entries.sort();
+
+    let mut tags = String::from("!_TAG_FILE_FORMAT\t2\t//\n!_TAG_FILE_SORTED\t1\t//\n");
+    for (name, line) in entries {
+        tags.push_str(&format!("{name}\t{asm_file}\t{line};\"\tl\n"));
+    }
+
+    tags
+}
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a String> {
+    let position = args.iter().position(|arg| arg == flag)?;
+    args.get(position + 1)
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() == 1 || args[1].len() == 0 {
+    if args.len() == 1 || args[1].is_empty() {
         panic!("No filename provided");
     }
 
-    let file = read(&args[1]).expect("could not read input file");
+    if args[1] == "decode" {
+        for line in decode_hex(&args[2..].join(" ")) {
+            println!("{line}");
+        }
+        return;
+    }
+
+    if args[1] == "bench" {
+        let bin =
+            read(args.get(2).expect("No filename provided")).expect("could not read input file");
+        run_bench(&bin);
+        return;
+    }
+
+    if args[1] == "encode" {
+        panic!("encode is not supported, there is no assembler yet");
+    }
+
+    let mut file = if args[1] == "-" {
+        let mut input = Vec::new();
+        stdin()
+            .read_to_end(&mut input)
+            .expect("could not read standard input");
+        input
+    } else {
+        read(&args[1]).expect("could not read input file")
+    };
+
+    if args.contains(&String::from("--hex-input")) {
+        file = parse_hex_text(&String::from_utf8_lossy(&file));
+    }
+
+    if args.contains(&String::from("--toolchain-banners")) {
+        let toolchains = scan_toolchain_banners(&file);
+        if toolchains.is_empty() {
+            println!("no known toolchain banner found");
+        } else {
+            println!("toolchain banner found: {}", toolchains.join(", "));
+        }
+        return;
+    }
 
-    let asm = parse_bin(file);
+    if let Some(coverage_file) = flag_value(&args, "--coverage") {
+        let map = coverage_map(&file);
+        write(coverage_file, &map).expect("error trying to write coverage file");
+        eprint!("{}", coverage_summary(&map));
+    }
+
+    if args.contains(&String::from("--segments")) {
+        print!("{}", segment_usage_report(&file));
+        return;
+    }
+
+    let annotations = match flag_value(&args, "--annotations") {
+        Some(annotations_file) => parse_annotations(
+            &read_to_string(annotations_file).expect("could not read annotations file"),
+        ),
+        None => Annotations::default(),
+    };
+
+    let asm = if args.contains(&String::from("--ne")) {
+        let mut executable = parse_ne(&file);
+        merge_ne_annotations(&mut executable, annotations);
+        ne_listing(&executable, &file)
+    } else if args.contains(&String::from("--coverage")) {
+        partial_listing(&file, &annotations)
+    } else {
+        listing(&decode_bin(&file), &annotations)
+    };
 
     if args.contains(&String::from("--stdio")) {
+        if args.contains(&String::from("--tags")) {
+            panic!("--tags needs the listing written to a file, it cannot be used with --stdio");
+        }
         println!("{asm}");
         return;
     }
 
+    if let Some(tags_file) = flag_value(&args, "--tags") {
+        write(tags_file, tags(&asm, "output")).expect("error trying to write tags file");
+    }
+
     // maybe in the future I'll write a proper args parser
     // and then add a -o, --output argument and only
     // generate an output file if it's set and use its
@@ -662,6 +1575,10 @@ mod tests {
             .collect()
     }
 
+    fn parse_bin(bin: Vec<u8>) -> String {
+        listing(&decode_bin(&bin), &Annotations::default())
+    }
+
     #[test]
     fn add_positive_immediate_to_accumulator() {
         assert_eq!(
@@ -702,6 +1619,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn mov_register_to_register() {
+        assert_eq!(
+            parse_bin(hex_to_bin("89d9").unwrap()),
+            "bits 16\n\n\nmov cx, bx"
+        );
+    }
+
+    #[test]
+    fn mov_displaced_memory_to_register() {
+        assert_eq!(
+            parse_bin(hex_to_bin("8b4604").unwrap()),
+            "bits 16\n\n\nmov ax, [bp + 4]"
+        );
+    }
+
     #[test]
     fn comp_register_and_memory() {
         assert_eq!(
@@ -725,4 +1658,291 @@ mod tests {
             "bits 16\n\n\ncmp ax, 1000"
         )
     }
+
+    #[test]
+    fn segment_report_warns_on_bp_addressing() {
+        assert_eq!(
+            segment_usage_report(&hex_to_bin("3b188b4604").unwrap()),
+            "default ds: 1 access\ndefault ss: 1 access\noverride es: 0 accesses\noverride cs: 0 accesses\noverride ss: 0 accesses\noverride ds: 0 accesses\nes loaded 0 times, stored 0 times\ncs loaded 0 times, stored 0 times\nss loaded 0 times, stored 0 times\nds loaded 0 times, stored 0 times\nwarning: 0x2: ss-relative data access without override: mov ax, [bp + 4]\n"
+        );
+    }
+
+    #[test]
+    fn segment_report_counts_overrides_and_stops_at_unknown_opcodes() {
+        assert_eq!(
+            segment_usage_report(&hex_to_bin("268b07368b460455").unwrap()),
+            "default ds: 0 accesses\ndefault ss: 0 accesses\noverride es: 1 access\noverride cs: 0 accesses\noverride ss: 1 access\noverride ds: 0 accesses\nes loaded 0 times, stored 0 times\ncs loaded 0 times, stored 0 times\nss loaded 0 times, stored 0 times\nds loaded 0 times, stored 0 times\n; 1 undecoded byte from offset 0x7\n"
+        );
+    }
+
+    #[test]
+    fn segment_report_counts_segment_register_moves() {
+        assert_eq!(
+            segment_usage_report(&hex_to_bin("8ed88c0655018cc0").unwrap()),
+            "default ds: 1 access\ndefault ss: 0 accesses\noverride es: 0 accesses\noverride cs: 0 accesses\noverride ss: 0 accesses\noverride ds: 0 accesses\nes loaded 0 times, stored 2 times\ncs loaded 0 times, stored 0 times\nss loaded 0 times, stored 0 times\nds loaded 1 time, stored 0 times\n"
+        );
+    }
+
+    #[test]
+    fn segment_register_moves_in_listing() {
+        assert_eq!(
+            parse_bin(hex_to_bin("8ed88c0655018c5e04").unwrap()),
+            "bits 16\n\n\nmov ds, ax\nmov [341], es\nmov [bp + 4], ds"
+        );
+    }
+
+    #[test]
+    fn segment_override_prefix_in_listing() {
+        assert_eq!(
+            parse_bin(hex_to_bin("268b072e89d9").unwrap()),
+            "bits 16\n\n\nmov ax, [es:bx]\ncs mov cx, bx"
+        );
+    }
+
+    #[test]
+    fn stacked_segment_override_prefixes_keep_the_last_one() {
+        assert_eq!(decode_hex("26 36 8b 07"), vec!["mov ax, [ss:bx]"]);
+
+        let mut bin = vec![0x2e; 100_000];
+        bin.extend_from_slice(&hex_to_bin("8b07").unwrap());
+        let instructions = decode_prefix(&bin);
+        assert_eq!(instructions.len(), 1);
+        assert_eq!(instructions[0].text, "mov ax, [cs:bx]");
+    }
+
+    #[test]
+    fn turbo_c_startup_banner_is_found() {
+        let mut bin = hex_to_bin("05e803").unwrap();
+        bin.extend_from_slice(b"Turbo-C - Copyright (c) 1988 Borland Intl.");
+        assert_eq!(scan_toolchain_banners(&bin), vec!["Turbo C"]);
+    }
+
+    #[test]
+    fn runtime_error_message_alone_is_not_turbo_pascal() {
+        assert!(scan_toolchain_banners(b"Runtime error 201 at 0000:0042.").is_empty());
+        assert!(scan_toolchain_banners(b"Portions Copyright (c) 1983 Acme").is_empty());
+        assert_eq!(
+            scan_toolchain_banners(b"Portions Copyright (c) 1983,92 Borland"),
+            vec!["Turbo Pascal"]
+        );
+    }
+
+    #[test]
+    fn microsoft_copyright_is_not_microsoft_c() {
+        assert!(scan_toolchain_banners(b"Copyright Microsoft Corporation 1991").is_empty());
+    }
+
+    #[test]
+    fn coverage_marks_code_unknown_and_padding() {
+        assert_eq!(
+            coverage_map(&hex_to_bin("05e803f4e8ffff").unwrap()),
+            vec![1, 1, 1, 0, 0, 2, 2]
+        );
+    }
+
+    #[test]
+    fn coverage_listing_survives_unsupported_opcodes() {
+        let bin = hex_to_bin("05e803f4e8ffff").unwrap();
+        assert_eq!(coverage_map(&bin), vec![1, 1, 1, 0, 0, 2, 2]);
+        assert_eq!(
+            partial_listing(&bin, &Annotations::default()),
+            "bits 16\n\n\nadd ax, 1000\n; 4 undecoded bytes from offset 0x3"
+        );
+    }
+
+    #[test]
+    fn coverage_keeps_trailing_fill_byte_of_an_instruction_as_code() {
+        assert_eq!(coverage_map(&hex_to_bin("b0ff").unwrap()), vec![1, 1]);
+        assert_eq!(
+            coverage_map(&hex_to_bin("b0ffffff").unwrap()),
+            vec![1, 1, 2, 2]
+        );
+        assert_eq!(
+            coverage_map(&hex_to_bin("05e80300000000").unwrap()),
+            vec![1, 1, 1, 2, 2, 2, 2]
+        );
+    }
+
+    #[test]
+    fn annotations_off_instruction_boundaries_are_kept() {
+        let annotations = parse_annotations("1: mid\n50: ; past the end\n3: ; todo");
+        assert_eq!(
+            listing(
+                &decode_bin(&hex_to_bin("05e80383fe02").unwrap()),
+                &annotations
+            ),
+            "bits 16\n\n\nadd ax, 1000\ncmp word si, 2 ; todo\n; 0x1: mid\n; 0x50: ; past the end"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid annotation label: loop")]
+    fn annotation_labels_cannot_be_reserved_words() {
+        parse_annotations("3: loop");
+    }
+
+    #[test]
+    fn annotations_add_labels_and_comments() {
+        let annotations = parse_annotations("0: start\n0x3: ; compare with 2\n");
+        assert_eq!(
+            listing(
+                &decode_bin(&hex_to_bin("05e80383fe02").unwrap()),
+                &annotations
+            ),
+            "bits 16\n\n\nstart:\nadd ax, 1000\ncmp word si, 2 ; compare with 2"
+        );
+    }
+
+    #[test]
+    fn hex_text_accepts_plain_and_xxd_input() {
+        assert_eq!(
+            parse_hex_text("89 d9 b8 e8 03"),
+            vec![0x89, 0xd9, 0xb8, 0xe8, 0x03]
+        );
+        assert_eq!(
+            parse_hex_text("00000000: 89d9 b8e8 03                             .....\n"),
+            vec![0x89, 0xd9, 0xb8, 0xe8, 0x03]
+        );
+    }
+
+    // a module with one 7 byte code segment at `sector`, an entry point at
+    // its start and one export named MAIN at offset 3
+    fn sample_ne(alignment_shift: u8, sector: u8) -> Vec<u8> {
+        let mut bin = vec![0; 0xa0];
+        bin[0..2].copy_from_slice(b"MZ");
+        bin[0x3c] = 0x40;
+
+        // NE header
+        bin[0x40..0x42].copy_from_slice(b"NE");
+        bin[0x44] = 0x56; // entry table
+        bin[0x46] = 6; // entry table length
+        bin[0x56] = 1; // entry point segment
+        bin[0x5c] = 1; // segment count
+        bin[0x62] = 0x40; // segment table
+        bin[0x66] = 0x48; // resident names
+        bin[0x72] = alignment_shift;
+
+        bin[0x80..0x88].copy_from_slice(&[sector, 0, 7, 0, 0, 0, 7, 0]);
+        bin[0x88..0x96].copy_from_slice(b"\x03FOO\0\0\x04MAIN\x01\0\0");
+        bin[0x96..0x9c].copy_from_slice(&[1, 1, 1, 3, 0, 0]);
+        bin
+    }
+
+    #[test]
+    fn ne_segments_are_listed_with_entry_labels() {
+        let mut bin = sample_ne(4, 0x0a);
+        bin.extend_from_slice(&hex_to_bin("05e80383fe02f4").unwrap());
+
+        assert_eq!(
+            ne_listing(&parse_ne(&bin), &bin),
+            "bits 16\n\n\n; segment 1 (code), 7 bytes at file offset 0xa0\nsection seg1 vstart=0\n\nstart:\nadd ax, 1000\nMAIN:\ncmp word si, 2\n; 1 undecoded byte from offset 0x6"
+        );
+    }
+
+    #[test]
+    fn ne_alignment_shift_of_zero_means_512_byte_sectors() {
+        let bin = sample_ne(0, 1);
+        assert_eq!(parse_ne(&bin).segments[0].file_offset, Some(0x200));
+    }
+
+    #[test]
+    fn ne_segment_without_file_data_is_not_decoded() {
+        let bin = sample_ne(4, 0);
+        let executable = parse_ne(&bin);
+        assert_eq!(executable.segments[0].file_offset, None);
+        assert_eq!(
+            ne_listing(&executable, &bin),
+            "bits 16\n\n\n; segment 1 (code), 7 bytes with no data in the file\nsection seg1 vstart=0\n\n; 0x0: start\n; 0x3: MAIN"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "outside every NE segment")]
+    fn ne_annotations_are_not_placed_in_segments_without_file_data() {
+        let bin = sample_ne(4, 0);
+        let mut executable = parse_ne(&bin);
+        merge_ne_annotations(&mut executable, parse_annotations("3: header"));
+    }
+
+    #[test]
+    fn ne_exports_past_the_decoded_code_are_kept_as_comments() {
+        let executable = NeExecutable {
+            segments: vec![NeSegment {
+                number: 1,
+                file_offset: Some(0),
+                length: 4,
+                is_data: false,
+            }],
+            labels: HashMap::from([(1, parse_annotations("3: WEP"))]),
+        };
+        assert_eq!(
+            ne_listing(&executable, &hex_to_bin("05e80355").unwrap()),
+            "bits 16\n\n\n; segment 1 (code), 4 bytes at file offset 0x0\nsection seg1 vstart=0\n\nadd ax, 1000\n; 1 undecoded byte from offset 0x3\n; 0x3: WEP"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Malformed NE header")]
+    fn truncated_ne_header_is_reported() {
+        let mut bin = vec![0; 0x42];
+        bin[0..2].copy_from_slice(b"MZ");
+        bin[0x3c] = 0x40;
+        bin[0x40..0x42].copy_from_slice(b"NE");
+        parse_ne(&bin);
+    }
+
+    #[test]
+    fn decode_hex_reports_incomplete_instruction() {
+        assert_eq!(decode_hex("83fe02"), vec!["cmp word si, 2"]);
+        assert_eq!(
+            decode_hex("83fe02 05e8"),
+            vec![
+                "cmp word si, 2",
+                "; 2 undecoded bytes from offset 0x3: 05 e8"
+            ]
+        );
+    }
+
+    #[test]
+    fn bench_stats_summarize_samples() {
+        let stats = bench_stats(&[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(stats.mean, 2.5);
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.max, 4.0);
+        assert_eq!(stats.stddev, 1.25_f64.sqrt());
+    }
+
+    #[test]
+    fn ne_annotations_use_file_offsets() {
+        let mut executable = NeExecutable {
+            segments: vec![NeSegment {
+                number: 1,
+                file_offset: Some(0xa0),
+                length: 6,
+                is_data: false,
+            }],
+            labels: HashMap::new(),
+        };
+        merge_ne_annotations(
+            &mut executable,
+            parse_annotations("a3: ; compare si\na0: begin"),
+        );
+
+        let labels = &executable.labels[&1];
+        assert_eq!(labels.names[&0], "begin");
+        assert_eq!(labels.comments[&3], "compare si");
+    }
+
+    #[test]
+    fn tags_point_labels_at_listing_lines() {
+        let annotations = parse_annotations("3: check\n0: start");
+        let asm = listing(
+            &decode_bin(&hex_to_bin("05e80383fe02").unwrap()),
+            &annotations,
+        );
+        assert_eq!(
+            tags(&asm, "output"),
+            "!_TAG_FILE_FORMAT\t2\t//\n!_TAG_FILE_SORTED\t1\t//\ncheck\toutput\t6;\"\tl\nstart\toutput\t4;\"\tl\n"
+        );
+    }
 }